@@ -65,65 +65,181 @@ impl Base {
 }
 
 /// Parse the numeric part of the `\xHHH` and `\0NNN` escape sequences
-fn parse_code(input: &mut &[u8], base: Base) -> Option<u8> {
-    // All arithmetic on `ret` needs to be wrapping, because octal input can
-    // take 3 digits, which is 9 bits, and therefore more than what fits in a
-    // `u8`. GNU just seems to wrap these values.
-    // Note that if we instead make `ret` a `u32` and use `char::from_u32` will
-    // yield incorrect results because it will interpret values larger than
-    // `u8::MAX` as unicode.
-    let [c, rest @ ..] = input else { return None };
-    let mut ret = base.convert_digit(*c)?;
-    *input = rest;
+///
+/// A `_` between digits is a no-op separator that doesn't count against
+/// `max_digits`. A leading `_`, before any digit has been read, is *not*
+/// a separator: it is rejected, same as `parse_unicode` does for `\u_1234`.
+/// (The `\0_17` case is handled by the caller, which consumes one leading
+/// `_` itself before calling this function.)
+///
+/// Returns the parsed byte together with whether the full-width value
+/// wrapped past `u8::MAX` (only possible for the 3-digit octal form), so
+/// that strict mode can flag the ambiguous case.
+fn parse_code(input: &mut &[u8], base: Base) -> Option<(u8, bool)> {
+    // `ret` is widened to `u32` so we can detect whether octal input (which
+    // can take 3 digits, i.e. 9 bits) overflowed a `u8` instead of silently
+    // wrapping. GNU just wraps these values, which is what we still do by
+    // truncating `ret` to `u8` below.
+    let mut ret: u32 = 0;
+    let mut n_digits = 0;
 
-    for _ in 1..base.max_digits() {
+    while n_digits < base.max_digits() {
         let [c, rest @ ..] = input else { break };
+        if *c == b'_' && n_digits > 0 {
+            *input = rest;
+            continue;
+        }
         let Some(n) = base.convert_digit(*c) else {
             break;
         };
-        ret = ret.wrapping_mul(base.as_base()).wrapping_add(n);
+        ret = ret * base.as_base() as u32 + n as u32;
+        n_digits += 1;
         *input = rest;
     }
 
-    Some(ret)
+    if n_digits == 0 {
+        return None;
+    }
+
+    Some((ret as u8, ret > u8::MAX as u32))
 }
 
 // spell-checker:disable-next
 /// Parse `\uHHHH` and `\UHHHHHHHH`
-// TODO: This should print warnings and possibly halt execution when it fails to parse
-// TODO: If the character cannot be converted to u32, the input should be printed.
-fn parse_unicode(input: &mut &[u8], digits: u8) -> Option<char> {
-    let (c, rest) = input.split_first()?;
-    let mut ret = Base::Hex.convert_digit(*c)? as u32;
+fn parse_unicode(input: &mut &[u8], digits: u8) -> Result<char, EscapeError> {
+    let mut consumed = Vec::new();
+
+    let Some((c, rest)) = input.split_first() else {
+        return Err(EscapeError::MissingHexDigits { digits: consumed });
+    };
+    let Some(n) = Base::Hex.convert_digit(*c) else {
+        return Err(EscapeError::MissingHexDigits { digits: consumed });
+    };
+    let mut ret = n as u32;
+    consumed.push(*c);
     *input = rest;
 
-    for _ in 1..digits {
-        let (c, rest) = input.split_first()?;
-        let n = Base::Hex.convert_digit(*c)?;
+    let mut n_digits = 1;
+    while n_digits < digits {
+        let Some((c, rest)) = input.split_first() else {
+            return Err(EscapeError::MissingHexDigits { digits: consumed });
+        };
+        if *c == b'_' {
+            consumed.push(*c);
+            *input = rest;
+            continue;
+        }
+        let Some(n) = Base::Hex.convert_digit(*c) else {
+            return Err(EscapeError::MissingHexDigits { digits: consumed });
+        };
+        consumed.push(*c);
         ret = ret
             .wrapping_mul(Base::Hex.as_base() as u32)
             .wrapping_add(n as u32);
+        n_digits += 1;
         *input = rest;
     }
 
-    char::from_u32(ret)
+    char::from_u32(ret).ok_or(EscapeError::InvalidCodePoint {
+        value: ret,
+        digits: consumed,
+    })
 }
 
-/// Represents an invalid escape sequence.
+// spell-checker:disable-next
+/// Parse the brace form `\u{HHHHHH}` (1 to 6 significant hex digits).
+///
+/// Like the fixed-width `\uHHHH` / `\UHHHHHHHH` forms, this rejects
+/// malformed input with an `EscapeError` instead of producing `'\0'`: empty
+/// braces, an unterminated sequence, more than six digits, surrogate code
+/// points and values above `0x10FFFF` are all errors.
+///
+/// A `_` between digits is a no-op separator, as in `parse_code`.
+fn parse_unicode_brace(input: &mut &[u8]) -> Result<char, EscapeError> {
+    let mut digits = Vec::new();
+    let mut ret: u32 = 0;
+    let mut n_digits = 0u8;
+
+    loop {
+        let [c, rest @ ..] = input else {
+            return Err(EscapeError::UnterminatedBrace { digits });
+        };
+        match *c {
+            b'}' => {
+                *input = rest;
+                break;
+            }
+            b'_' if n_digits > 0 => {
+                digits.push(*c);
+                *input = rest;
+                continue;
+            }
+            _ => {}
+        }
+        let Some(digit) = Base::Hex.convert_digit(*c) else {
+            return Err(EscapeError::MissingHexDigits { digits });
+        };
+        digits.push(*c);
+        *input = rest;
+        if n_digits == 6 {
+            return Err(EscapeError::TooManyDigits { digits });
+        }
+        ret = ret * 16 + digit as u32;
+        n_digits += 1;
+    }
+
+    if n_digits == 0 {
+        return Err(EscapeError::MissingHexDigits { digits });
+    }
+
+    char::from_u32(ret).ok_or(EscapeError::InvalidCodePoint { value: ret, digits })
+}
+
+/// Represents an invalid escape sequence, recording enough context (the raw
+/// bytes that were read) for a caller to report a GNU-style diagnostic, e.g.
+/// `invalid universal character name \uD800`.
 #[derive(Debug)]
-pub struct EscapeError {}
+pub enum EscapeError {
+    /// A `\x`, `\u` or `\U` escape had no (or not enough) hex digits
+    /// following it. Carries whatever digits (and separators) were read
+    /// before parsing gave up.
+    MissingHexDigits { digits: Vec<u8> },
+    /// A `\u{...}` escape had more than six hex digits.
+    TooManyDigits { digits: Vec<u8> },
+    /// A `\u{...}` escape was not closed with a `}` before the input ended.
+    UnterminatedBrace { digits: Vec<u8> },
+    /// The digits parsed to a value that is not a valid `char`: a UTF-16
+    /// surrogate half, or a value above `0x10FFFF`.
+    InvalidCodePoint { value: u32, digits: Vec<u8> },
+    /// In strict mode, a 3-digit `\NNN` or `\0NNN` octal escape whose value
+    /// wraps past `u8::MAX` (9 bits of octal digits don't fit in a byte), e.g.
+    /// `\777` (511), which would otherwise silently become `0xff`; `\xHH` is
+    /// unambiguous and should be used instead.
+    AmbiguousOctal { digits: Vec<u8> },
+}
 
 /// Parse an escape sequence, like `\n` or `\xff`, etc.
+///
+/// When `strict` is set, ambiguous C-style `\NNN` / `\0NNN` octal escapes that wrap
+/// past `u8::MAX` are rejected with [`EscapeError::AmbiguousOctal`] instead
+/// of silently producing a wrapped byte (see that variant's docs).
 pub fn parse_escape_code(
     rest: &mut &[u8],
     zero_octal_parsing: OctalParsing,
+    strict: bool,
 ) -> Result<EscapedChar, EscapeError> {
     if let [c, new_rest @ ..] = rest {
         // This is for the \NNN syntax for octal sequences.
         // Note that '0' is intentionally omitted because that
         // would be the \0NNN syntax.
         if let b'1'..=b'7' = c {
-            if let Some(parsed) = parse_code(rest, Base::Oct(OctalParsing::ThreeDigits)) {
+            let before = *rest;
+            if let Some((parsed, wrapped)) = parse_code(rest, Base::Oct(OctalParsing::ThreeDigits))
+            {
+                if strict && wrapped {
+                    let digits = before[..before.len() - rest.len()].to_vec();
+                    return Err(EscapeError::AmbiguousOctal { digits });
+                }
                 return Ok(EscapedChar::Byte(parsed));
             }
         }
@@ -142,20 +258,196 @@ pub fn parse_escape_code(
             b't' => Ok(EscapedChar::Byte(b'\t')),
             b'v' => Ok(EscapedChar::Byte(b'\x0b')),
             b'x' => {
-                if let Some(c) = parse_code(rest, Base::Hex) {
-                    Ok(EscapedChar::Byte(c))
+                let before = *rest;
+                match parse_code(rest, Base::Hex) {
+                    Some((c, _)) => Ok(EscapedChar::Byte(c)),
+                    None => {
+                        let digits = before[..before.len() - rest.len()].to_vec();
+                        Err(EscapeError::MissingHexDigits { digits })
+                    }
+                }
+            }
+            b'0' => {
+                let before = *rest;
+                // `\0_17` is the same as `\017`: a single `_` immediately
+                // after the `0` prefix is allowed, unlike a leading `_`
+                // before any digit elsewhere (see `parse_code`). Only strip
+                // it if an octal digit actually follows, so `\0_z` leaves
+                // the `_` in place as literal text instead of swallowing it.
+                if let [b'_', d, ..] = rest {
+                    if matches!(d, b'0'..=b'7') {
+                        *rest = &rest[1..];
+                    }
+                }
+                match parse_code(rest, Base::Oct(zero_octal_parsing)) {
+                    Some((c, wrapped)) => {
+                        if strict && wrapped {
+                            let digits = before[..before.len() - rest.len()].to_vec();
+                            Err(EscapeError::AmbiguousOctal { digits })
+                        } else {
+                            Ok(EscapedChar::Byte(c))
+                        }
+                    }
+                    None => Ok(EscapedChar::Byte(b'\0')),
+                }
+            }
+            b'u' => {
+                if let [b'{', new_rest @ ..] = rest {
+                    *rest = new_rest;
+                    parse_unicode_brace(rest).map(EscapedChar::Char)
+                } else {
+                    parse_unicode(rest, 4).map(EscapedChar::Char)
+                }
+            }
+            b'U' => {
+                if let [b'{', new_rest @ ..] = rest {
+                    *rest = new_rest;
+                    parse_unicode_brace(rest).map(EscapedChar::Char)
                 } else {
-                    Err(EscapeError {})
+                    parse_unicode(rest, 8).map(EscapedChar::Char)
                 }
             }
-            b'0' => Ok(EscapedChar::Byte(
-                parse_code(rest, Base::Oct(zero_octal_parsing)).unwrap_or(b'\0'),
-            )),
-            b'u' => Ok(EscapedChar::Char(parse_unicode(rest, 4).unwrap_or('\0'))),
-            b'U' => Ok(EscapedChar::Char(parse_unicode(rest, 8).unwrap_or('\0'))),
             c => Ok(EscapedChar::Backslash(*c)),
         }
     } else {
         Ok(EscapedChar::Byte(b'\\'))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn byte(mut input: &[u8]) -> u8 {
+        match parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap() {
+            EscapedChar::Byte(b) => b,
+            other => panic!("expected a byte, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn underscore_immediately_after_prefix_is_a_separator() {
+        assert_eq!(byte(b"0_17"), byte(b"017"));
+    }
+
+    #[test]
+    fn leading_underscore_before_non_octal_digit_is_literal_text() {
+        // No octal digit follows the `_`, so it must survive as literal
+        // text rather than being silently swallowed.
+        let mut input: &[u8] = b"0_z";
+        let c = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap();
+        assert!(matches!(c, EscapedChar::Byte(b'\0')));
+        assert_eq!(input, b"_z");
+    }
+
+    #[test]
+    fn underscore_between_hex_digits_is_a_separator() {
+        assert_eq!(byte(b"x1_b"), byte(b"x1b"));
+    }
+
+    #[test]
+    fn leading_underscore_before_any_hex_digit_is_rejected() {
+        let mut input: &[u8] = b"x_1b";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::MissingHexDigits { digits } if digits.is_empty()));
+    }
+
+    #[test]
+    fn missing_hex_digits_reports_leftover_text() {
+        let mut input: &[u8] = b"x";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::MissingHexDigits { digits } if digits.is_empty()));
+
+        let mut input: &[u8] = b"u12";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::MissingHexDigits { digits } if digits == b"12"));
+    }
+
+    #[test]
+    fn strict_mode_flags_wrapping_zero_octal_escape() {
+        // \0777 = 0o777 = 511, which wraps past u8::MAX.
+        let mut input: &[u8] = b"0777";
+        let err =
+            parse_escape_code(&mut input, OctalParsing::ThreeDigits, true).unwrap_err();
+        assert!(matches!(err, EscapeError::AmbiguousOctal { digits } if digits == b"777"));
+
+        // Non-strict mode keeps wrapping silently, matching \1-\7 behavior.
+        let mut input: &[u8] = b"0777";
+        assert_eq!(
+            byte_with(&mut input, OctalParsing::ThreeDigits, false),
+            0o777u32 as u8
+        );
+    }
+
+    #[test]
+    fn strict_mode_flags_wrapping_plain_octal_escape() {
+        // \777 = 0o777 = 511, which wraps past u8::MAX, same as \0777.
+        let mut input: &[u8] = b"777";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, true).unwrap_err();
+        assert!(matches!(err, EscapeError::AmbiguousOctal { digits } if digits == b"777"));
+
+        // Non-strict mode keeps wrapping silently.
+        let mut input: &[u8] = b"777";
+        assert_eq!(
+            byte_with(&mut input, OctalParsing::TwoDigits, false),
+            0o777u32 as u8
+        );
+    }
+
+    fn byte_with(input: &mut &[u8], octal: OctalParsing, strict: bool) -> u8 {
+        match parse_escape_code(input, octal, strict).unwrap() {
+            EscapedChar::Byte(b) => b,
+            other => panic!("expected a byte, got {other:?}"),
+        }
+    }
+
+    fn char_of(mut input: &[u8]) -> char {
+        match parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap() {
+            EscapedChar::Char(c) => c,
+            other => panic!("expected a char, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn brace_form_accepts_one_to_six_digits() {
+        assert_eq!(char_of(b"u{1F600}"), '\u{1F600}');
+        assert_eq!(char_of(b"u{41}"), 'A');
+    }
+
+    #[test]
+    fn brace_form_rejects_empty_braces() {
+        let mut input: &[u8] = b"u{}";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::MissingHexDigits { digits } if digits.is_empty()));
+    }
+
+    #[test]
+    fn brace_form_rejects_unterminated_sequence() {
+        let mut input: &[u8] = b"u{41";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::UnterminatedBrace { digits } if digits == b"41"));
+    }
+
+    #[test]
+    fn brace_form_rejects_more_than_six_digits() {
+        let mut input: &[u8] = b"u{1000000}";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::TooManyDigits { digits } if digits == b"1000000"));
+    }
+
+    #[test]
+    fn brace_form_rejects_surrogates_and_out_of_range_values() {
+        let mut input: &[u8] = b"u{D800}";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::InvalidCodePoint { value: 0xD800, .. }));
+
+        let mut input: &[u8] = b"u{110000}";
+        let err = parse_escape_code(&mut input, OctalParsing::TwoDigits, false).unwrap_err();
+        assert!(matches!(err, EscapeError::InvalidCodePoint { value: 0x110000, .. }));
+    }
+
+    #[test]
+    fn brace_form_allows_underscore_separators() {
+        assert_eq!(char_of(b"u{1_F600}"), '\u{1F600}');
+    }
+}